@@ -2,14 +2,27 @@
 #![no_std]
 extern crate constant_time_eq;
 extern crate generic_array;
+#[cfg(feature = "cipher")]
+extern crate cipher;
+#[cfg(feature = "rand_core")]
+extern crate rand_core;
+#[cfg(feature = "subtle")]
+extern crate subtle;
 
 use constant_time_eq::constant_time_eq;
+#[cfg(feature = "rand_core")]
+use rand_core::{CryptoRng, RngCore};
+#[cfg(feature = "subtle")]
+use subtle::{Choice, ConstantTimeEq};
 use generic_array::{GenericArray, ArrayLength};
 use generic_array::typenum::Unsigned;
 
 #[cfg(feature = "dev")]
 pub mod dev;
 
+#[cfg(feature = "cipher")]
+pub use cipher::{BlockCipher, NewBlockCipher};
+
 /// Error type for signaling failed MAC verification
 #[derive(Default, Debug, Copy, Clone, Eq, PartialEq)]
 pub struct MacError;
@@ -18,16 +31,18 @@ pub struct MacError;
 #[derive(Default, Debug, Copy, Clone, Eq, PartialEq)]
 pub struct InvalidKeyLength;
 
-/// The `Mac` trait defines methods for a Message Authentication algorithm.
-pub trait Mac: core::marker::Sized {
-    type OutputSize: ArrayLength<u8>;
+/// Key for an algorithm that implements the [`NewMac`] trait.
+pub type Key<M> = GenericArray<u8, <M as NewMac>::KeySize>;
+
+/// The `NewMac` trait defines the key-based construction of a MAC algorithm.
+pub trait NewMac: core::marker::Sized {
     type KeySize: ArrayLength<u8>;
 
     /// Create a new MAC instance from key with fixed size.
-    fn new(key: &GenericArray<u8, Self::KeySize>) -> Self;
+    fn new(key: &Key<Self>) -> Self;
 
     /// Create a new MAC instance from variable sized key.
-    fn new_varkey(key: &[u8]) -> Result<Self, InvalidKeyLength> {
+    fn new_from_slice(key: &[u8]) -> Result<Self, InvalidKeyLength> {
         if key.len() != Self::KeySize::to_usize() {
             Err(InvalidKeyLength)
         } else {
@@ -35,15 +50,42 @@ pub trait Mac: core::marker::Sized {
         }
     }
 
+    /// Generate a correctly-sized random MAC key from the provided RNG.
+    #[cfg(feature = "rand_core")]
+    fn generate_key(mut rng: impl RngCore + CryptoRng) -> Key<Self> {
+        let mut key = Key::<Self>::default();
+        rng.fill_bytes(&mut key);
+        key
+    }
+}
+
+/// The `Mac` trait defines the streaming and verification part of a Message
+/// Authentication algorithm.
+pub trait Mac {
+    type OutputSize: ArrayLength<u8>;
+
     /// Process input data.
     fn input(&mut self, data: &[u8]);
 
+    /// Process input data in a chained manner, consuming and returning `self`.
+    ///
+    /// Enables one-liner MAC computation, e.g.
+    /// `Hmac::new(k).chain(a).chain(b).result()`.
+    fn chain(mut self, data: &[u8]) -> Self where Self: Sized {
+        self.input(data);
+        self
+    }
+
+    /// Reset `Mac` instance to its initial state.
+    fn reset(&mut self);
+
     /// Obtain the result of a `Mac` computation as a `MacResult` and reset
     /// `Mac` instance.
     fn result(&mut self) -> MacResult<Self::OutputSize>;
 
     /// Check if code is correct for the processed input and reset
     /// `Mac` instance.
+    #[cfg(not(feature = "subtle"))]
     fn verify(&mut self, code: &[u8]) -> Result<(), MacError> {
         if Self::OutputSize::to_usize() != code.len() {
             Err(MacError)
@@ -56,6 +98,90 @@ pub trait Mac: core::marker::Sized {
             }
         }
     }
+
+    /// Check if code is correct for the processed input and reset
+    /// `Mac` instance.
+    ///
+    /// This is a thin wrapper around [`verify_ct`](Self::verify_ct) which
+    /// collapses the constant-time `Choice` into a `Result` only at the
+    /// boundary.
+    #[cfg(feature = "subtle")]
+    fn verify(&mut self, code: &[u8]) -> Result<(), MacError> {
+        if self.verify_ct(code).into() {
+            Ok(())
+        } else {
+            Err(MacError)
+        }
+    }
+
+    /// Check if code is correct for the processed input and reset `Mac`
+    /// instance, returning a constant-time `Choice` instead of a `Result`.
+    ///
+    /// Both a byte-length mismatch and a differing content produce an
+    /// all-zeros `Choice`, allowing callers to fold the verification outcome
+    /// into a larger constant-time decision without branching on tag validity.
+    #[cfg(feature = "subtle")]
+    fn verify_ct(&mut self, code: &[u8]) -> Choice {
+        let result = self.result();
+        if Self::OutputSize::to_usize() != code.len() {
+            Choice::from(0)
+        } else {
+            let expected = MacResult::new(GenericArray::clone_from_slice(code));
+            expected.ct_eq(&result)
+        }
+    }
+
+    /// Check if the first `tag.len()` bytes of the code are correct for the
+    /// processed input and reset `Mac` instance.
+    ///
+    /// Useful for protocols which transmit only a left-aligned prefix of the
+    /// full tag, e.g. truncated HMAC. Returns `MacError` if `tag` is empty or
+    /// longer than `OutputSize`.
+    fn verify_truncated_left(&mut self, tag: &[u8]) -> Result<(), MacError> {
+        let n = tag.len();
+        let code = self.result().code();
+        if n == 0 || n > Self::OutputSize::to_usize() {
+            return Err(MacError);
+        }
+        if constant_time_eq(&code[..n], tag) {
+            Ok(())
+        } else {
+            Err(MacError)
+        }
+    }
+
+    /// Check if the last `tag.len()` bytes of the code are correct for the
+    /// processed input and reset `Mac` instance.
+    ///
+    /// Useful for protocols which transmit only a right-aligned suffix of the
+    /// full tag. Returns `MacError` if `tag` is empty or longer than
+    /// `OutputSize`.
+    fn verify_truncated_right(&mut self, tag: &[u8]) -> Result<(), MacError> {
+        let n = tag.len();
+        let len = Self::OutputSize::to_usize();
+        let code = self.result().code();
+        if n == 0 || n > len {
+            return Err(MacError);
+        }
+        if constant_time_eq(&code[len - n..], tag) {
+            Ok(())
+        } else {
+            Err(MacError)
+        }
+    }
+}
+
+/// The `FromBlockCipher` trait constructs a MAC from a pre-keyed block cipher
+/// instance, for block-cipher-backed constructions such as CBC-MAC and CMAC.
+///
+/// This complements [`NewMac`], which constructs a MAC from raw key bytes; the
+/// MAC's `OutputSize` is expected to match the cipher's `BlockSize`.
+#[cfg(feature = "cipher")]
+pub trait FromBlockCipher {
+    type Cipher: BlockCipher + NewBlockCipher;
+
+    /// Create a new MAC instance from an already-keyed block cipher.
+    fn from_cipher(cipher: Self::Cipher) -> Self;
 }
 
 /// `MacResult` is a thin wrapper around bytes array which provides a safe `Eq`
@@ -79,6 +205,21 @@ impl<N> MacResult<N> where N: ArrayLength<u8> {
     }
 }
 
+#[cfg(feature = "subtle")]
+impl<N> ConstantTimeEq for MacResult<N> where N: ArrayLength<u8> {
+    fn ct_eq(&self, other: &MacResult<N>) -> Choice {
+        self.code[..].ct_eq(&other.code[..])
+    }
+}
+
+#[cfg(feature = "subtle")]
+impl<N> PartialEq for MacResult<N> where N: ArrayLength<u8> {
+    fn eq(&self, x: &MacResult<N>) -> bool {
+        self.ct_eq(x).into()
+    }
+}
+
+#[cfg(not(feature = "subtle"))]
 impl<N> PartialEq for MacResult<N> where N: ArrayLength<u8> {
     fn eq(&self, x: &MacResult<N>) -> bool {
         constant_time_eq(&self.code[..], &x.code[..])